@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use crate::{NetworkPacket, TCPFlags, TransportLayer};
+
+/// Matches specific TCP flag values; a `None` field means "don't care about this flag"
+#[derive(Debug, Clone, Default)]
+pub struct TcpFlagFilter {
+    pub syn: Option<bool>,
+    pub ack: Option<bool>,
+    pub fin: Option<bool>,
+    pub rst: Option<bool>,
+    pub psh: Option<bool>,
+    pub urg: Option<bool>,
+    pub ece: Option<bool>,
+    pub cwr: Option<bool>,
+}
+
+impl TcpFlagFilter {
+    fn matches(&self, flags: &TCPFlags) -> bool {
+        self.syn.is_none_or(|v| v == flags.syn)
+            && self.ack.is_none_or(|v| v == flags.ack)
+            && self.fin.is_none_or(|v| v == flags.fin)
+            && self.rst.is_none_or(|v| v == flags.rst)
+            && self.psh.is_none_or(|v| v == flags.psh)
+            && self.urg.is_none_or(|v| v == flags.urg)
+            && self.ece.is_none_or(|v| v == flags.ece)
+            && self.cwr.is_none_or(|v| v == flags.cwr)
+    }
+}
+
+/// A lightweight, post-parse predicate for selecting packets of interest
+///
+/// Unlike a BPF filter, this runs against already-parsed `NetworkPacket`s, so it
+/// can be composed and adjusted without recompiling or re-reading the capture.
+#[derive(Debug, Clone, Default)]
+pub struct PacketFilter {
+    pub source_ip_range: Option<(IpAddr, IpAddr)>,
+    pub destination_ip_range: Option<(IpAddr, IpAddr)>,
+    pub ports: Option<HashSet<u16>>,
+    pub tcp_flags: Option<TcpFlagFilter>,
+}
+
+impl PacketFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source_ip_range(mut self, low: IpAddr, high: IpAddr) -> Self {
+        self.source_ip_range = Some((low, high));
+        self
+    }
+
+    pub fn with_destination_ip_range(mut self, low: IpAddr, high: IpAddr) -> Self {
+        self.destination_ip_range = Some((low, high));
+        self
+    }
+
+    pub fn with_ports(mut self, ports: HashSet<u16>) -> Self {
+        self.ports = Some(ports);
+        self
+    }
+
+    pub fn with_tcp_flags(mut self, flags: TcpFlagFilter) -> Self {
+        self.tcp_flags = Some(flags);
+        self
+    }
+
+    /// Check whether a single packet satisfies every predicate configured on this filter
+    ///
+    /// Non-IP frames (e.g. ARP) never match a `PacketFilter`, since it is defined
+    /// entirely in terms of IP/TCP/UDP fields.
+    pub fn matches(&self, packet: &NetworkPacket) -> bool {
+        let (ip_layer, transport_layer, _) = match packet.body.as_ip() {
+            Some(layers) => layers,
+            None => return false,
+        };
+
+        if let Some((low, high)) = self.source_ip_range {
+            let addr = ip_layer.source_addr();
+            if addr < low || addr > high {
+                return false;
+            }
+        }
+
+        if let Some((low, high)) = self.destination_ip_range {
+            let addr = ip_layer.destination_addr();
+            if addr < low || addr > high {
+                return false;
+            }
+        }
+
+        let (source_port, destination_port) = match transport_layer {
+            TransportLayer::Tcp(tcp) => (tcp.source_port, tcp.destination_port),
+            TransportLayer::Udp(udp) => (udp.source_port, udp.destination_port),
+        };
+
+        if let Some(ports) = &self.ports {
+            if !ports.contains(&source_port) && !ports.contains(&destination_port) {
+                return false;
+            }
+        }
+
+        if let Some(flag_filter) = &self.tcp_flags {
+            match transport_layer {
+                TransportLayer::Tcp(tcp) => {
+                    if !flag_filter.matches(&tcp.flags) {
+                        return false;
+                    }
+                }
+                TransportLayer::Udp(_) => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// Apply a `PacketFilter` to already-parsed packets, keeping only the matches
+pub fn filter_packets(packets: &[NetworkPacket], filter: &PacketFilter) -> Vec<NetworkPacket> {
+    packets.iter().filter(|packet| filter.matches(packet)).cloned().collect()
+}
@@ -0,0 +1,6 @@
+pub mod filter;
+pub mod packet;
+pub mod parser;
+pub mod sessions;
+
+pub use packet::*;
@@ -1,21 +1,15 @@
-mod packet;
-mod parser;
-mod sessions;
-
 use std::path::Path;
 // for writing to files
 use std::fs::File;
 use std::io::prelude::*;
-use serde_json::Value;
 use serde_json::json;
 
-#[allow(unused_imports)]
-use packet::*;
-use parser::parse_pcap;
-use sessions::find_tcp_sessions;
+use net_traffic::parser::parse_pcap;
+use net_traffic::sessions::find_tcp_sessions;
+
 fn main() {
     let path = Path::new("src/example/pcap/rsasnakeoil2.pcap");
-    let res = match parse_pcap(path) {
+    let res = match parse_pcap(path, None) {
         Ok(res) => res,
         Err(e) => {
             println!("{:#?}", e);
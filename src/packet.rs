@@ -1,17 +1,137 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkPacket {
     pub timestamp: DateTime<Utc>,
     pub ethernet_layer: EthernetFrame,
-    pub ip_layer: IPv4Packet,
-    pub tcp_layer: TCPSegment,
-    pub application_layer: ApplicationData,
+    pub body: PacketBody,
+}
+
+/// Everything past the Ethernet header: either routed IP traffic, or an ARP frame
+///
+/// ARP doesn't carry an IP/transport layer, so it gets its own variant instead of
+/// forcing `ip_layer`/`transport_layer` fields that wouldn't make sense for it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PacketBody {
+    Ip {
+        ip_layer: IpLayer,
+        transport_layer: TransportLayer,
+        application_layer: ApplicationData,
+    },
+    Arp(ArpPacket),
+}
+
+impl PacketBody {
+    /// Borrow the IP/transport/application layers, if this frame carried routed IP traffic
+    pub fn as_ip(&self) -> Option<(&IpLayer, &TransportLayer, &ApplicationData)> {
+        match self {
+            PacketBody::Ip { ip_layer, transport_layer, application_layer } => {
+                Some((ip_layer, transport_layer, application_layer))
+            }
+            PacketBody::Arp(_) => None,
+        }
+    }
+
+    /// Borrow the ARP packet, if this frame was an ARP request or reply
+    pub fn as_arp(&self) -> Option<&ArpPacket> {
+        match self {
+            PacketBody::Arp(arp) => Some(arp),
+            PacketBody::Ip { .. } => None,
+        }
+    }
+}
+
+/// ARP packet (request or reply), addressed over Ethernet/IPv4
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArpPacket {
+    pub hardware_type: u16,
+    pub protocol_type: u16,
+    pub hardware_addr_len: u8,
+    pub protocol_addr_len: u8,
+    pub operation: ArpOperation,
+    pub sender_hardware_addr: [u8; 6],
+    pub sender_protocol_addr: [u8; 4],
+    pub target_hardware_addr: [u8; 6],
+    pub target_protocol_addr: [u8; 4],
+}
+
+/// ARP operation code
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ArpOperation {
+    Request,
+    Reply,
+    Other(u16),
+}
+
+/// Build an IP -> MAC mapping table by watching sender addresses announced in ARP
+/// traffic. Useful for labeling endpoints with their observed MAC address. Every MAC
+/// ever claimed for an IP is kept (not just the most recent), so a spoofed host
+/// claiming someone else's IP shows up as that IP mapping to more than one MAC
+/// instead of silently overwriting the legitimate owner's entry — see
+/// [`spoofed_addresses`] to check for that directly.
+pub fn build_arp_table(packets: &[NetworkPacket]) -> HashMap<IpAddr, HashSet<[u8; 6]>> {
+    let mut table: HashMap<IpAddr, HashSet<[u8; 6]>> = HashMap::new();
+
+    for packet in packets {
+        if let Some(arp) = packet.body.as_arp() {
+            table
+                .entry(IpAddr::V4(arp.sender_protocol_addr.into()))
+                .or_default()
+                .insert(arp.sender_hardware_addr);
+        }
+    }
+
+    table
+}
+
+/// IP addresses that have been announced by more than one MAC address in an ARP
+/// table built by [`build_arp_table`] — a sign of ARP spoofing.
+pub fn spoofed_addresses(arp_table: &HashMap<IpAddr, HashSet<[u8; 6]>>) -> Vec<IpAddr> {
+    arp_table
+        .iter()
+        .filter(|(_, macs)| macs.len() > 1)
+        .map(|(ip, _)| *ip)
+        .collect()
+}
+
+/// Layer 4 payload, TCP or UDP
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TransportLayer {
+    Tcp(TCPSegment),
+    Udp(UdpDatagram),
+}
+
+/// Layer 3 payload, IPv4 or IPv6
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum IpLayer {
+    V4(IPv4Packet),
+    V6(IPv6Packet),
+}
+
+impl IpLayer {
+    /// Source address as a standard library `IpAddr`, regardless of version
+    pub fn source_addr(&self) -> std::net::IpAddr {
+        match self {
+            IpLayer::V4(packet) => std::net::IpAddr::V4(packet.source_ip.into()),
+            IpLayer::V6(packet) => std::net::IpAddr::V6(packet.source_ip.into()),
+        }
+    }
+
+    /// Destination address as a standard library `IpAddr`, regardless of version
+    pub fn destination_addr(&self) -> std::net::IpAddr {
+        match self {
+            IpLayer::V4(packet) => std::net::IpAddr::V4(packet.destination_ip.into()),
+            IpLayer::V6(packet) => std::net::IpAddr::V6(packet.destination_ip.into()),
+        }
+    }
 }
 
 /// Ethernet (Layer 2) Frame
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EthernetFrame {
     pub source_mac: [u8; 6],
     pub destination_mac: [u8; 6],
@@ -20,7 +140,7 @@ pub struct EthernetFrame {
 }
 
 /// IPv4 (Layer 3) Packet
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IPv4Packet {
     pub version: u8,          // 4 for IPv4
     pub ihl: u8,             // Internet Header Length
@@ -39,15 +159,27 @@ pub struct IPv4Packet {
 }
 
 /// IPv4 Flags
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IPv4Flags {
     pub reserved: bool,      // Must be zero
     pub dont_fragment: bool,
     pub more_fragments: bool,
 }
 
+/// IPv6 (Layer 3) Packet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IPv6Packet {
+    pub traffic_class: u8,
+    pub flow_label: u32,     // 20 bits
+    pub payload_length: u16,
+    pub next_header: u8,     // same namespace as IPv4's protocol field
+    pub hop_limit: u8,
+    pub source_ip: [u8; 16],
+    pub destination_ip: [u8; 16],
+}
+
 /// TCP (Layer 4) Segment
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TCPSegment {
     pub source_port: u16,
     pub destination_port: u16,
@@ -61,8 +193,17 @@ pub struct TCPSegment {
     pub options: Vec<TCPOption>,
 }
 
+/// UDP (Layer 4) Datagram
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UdpDatagram {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub length: u16,
+    pub checksum: u16,
+}
+
 /// TCP Flags
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TCPFlags {
     pub fin: bool,          // Finish
     pub syn: bool,          // Synchronize
@@ -75,7 +216,7 @@ pub struct TCPFlags {
 }
 
 /// TCP Option
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TCPOption {
     pub kind: u8,
     pub length: u8,
@@ -83,14 +224,15 @@ pub struct TCPOption {
 }
 
 /// Application Layer Data
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApplicationData {
     pub protocol: ApplicationProtocol,
     pub payload: Vec<u8>,
 }
 
 /// Supported Application Protocols
-#[derive(Debug, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)] // these are protocol names, not ordinary identifiers
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ApplicationProtocol {
     HTTP,
     HTTPS,
@@ -105,27 +247,50 @@ pub enum ApplicationProtocol {
 impl NetworkPacket {
     /// Calculate the total size of the packet in bytes
     pub fn total_size(&self) -> usize {
-        14 + // Ethernet header (without FCS)
-        20 + self.ip_layer.options.len() + // IPv4 header
-        20 + self.tcp_layer.options.iter().map(|opt| opt.length as usize).sum::<usize>() + // TCP header
-        self.application_layer.payload.len() // Application data
+        let body_size = match &self.body {
+            PacketBody::Ip { ip_layer, transport_layer, application_layer } => {
+                let ip_header_size = match ip_layer {
+                    IpLayer::V4(ip) => 20 + ip.options.len(),
+                    IpLayer::V6(_) => 40, // fixed IPv6 header, extension headers not tracked separately
+                };
+                let transport_header_size = match transport_layer {
+                    TransportLayer::Tcp(tcp) => {
+                        20 + tcp.options.iter().map(|opt| opt.length as usize).sum::<usize>()
+                    }
+                    TransportLayer::Udp(_) => 8,
+                };
+                ip_header_size + transport_header_size + application_layer.payload.len()
+            }
+            // hardware/protocol type, lengths, operation, and two (hw, proto) address pairs
+            PacketBody::Arp(arp) => {
+                8 + 2 * (arp.hardware_addr_len as usize + arp.protocol_addr_len as usize)
+            }
+        };
+
+        14 + body_size // Ethernet header (without FCS)
     }
-    
+
     /// Check if packet is part of a TCP handshake
     pub fn is_handshake(&self) -> bool {
-        self.tcp_layer.flags.syn || self.tcp_layer.flags.fin
+        match self.body.as_ip() {
+            Some((_, TransportLayer::Tcp(tcp), _)) => tcp.flags.syn || tcp.flags.fin,
+            _ => false,
+        }
     }
-    
+
     /// Get the application protocol as a string
     pub fn get_protocol_string(&self) -> String {
-        match &self.application_layer.protocol {
-            ApplicationProtocol::HTTP => "HTTP".to_string(),
-            ApplicationProtocol::HTTPS => "HTTPS".to_string(),
-            ApplicationProtocol::FTP => "FTP".to_string(),
-            ApplicationProtocol::SSH => "SSH".to_string(),
-            ApplicationProtocol::SMTP => "SMTP".to_string(),
-            ApplicationProtocol::DNS => "DNS".to_string(),
-            ApplicationProtocol::Custom(proto) => proto.clone(),
+        match &self.body {
+            PacketBody::Ip { application_layer, .. } => match &application_layer.protocol {
+                ApplicationProtocol::HTTP => "HTTP".to_string(),
+                ApplicationProtocol::HTTPS => "HTTPS".to_string(),
+                ApplicationProtocol::FTP => "FTP".to_string(),
+                ApplicationProtocol::SSH => "SSH".to_string(),
+                ApplicationProtocol::SMTP => "SMTP".to_string(),
+                ApplicationProtocol::DNS => "DNS".to_string(),
+                ApplicationProtocol::Custom(proto) => proto.clone(),
+            },
+            PacketBody::Arp(_) => "ARP".to_string(),
         }
     }
 }
\ No newline at end of file
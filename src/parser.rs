@@ -1,18 +1,19 @@
-use std::process::Command;
+use std::fs::File;
+use std::io::{self, Read};
 use std::path::Path;
-use std::io::{self};
-use serde_json::{Value};
-use chrono::DateTime;
+
+use chrono::{DateTime, Utc};
+use etherparse::{InternetSlice, LinkSlice, SlicedPacket, TransportSlice};
+
 use crate::packet::*;
-use chrono::Utc;
 
 /// Error types for PCAP parsing
 #[derive(Debug)]
 pub enum PcapError {
     IoError(io::Error),
-    TsharkNotFound,
+    InvalidFormat(String),
     ParseError(String),
-    JsonError(serde_json::Error),
+    CaptureError(pcap::Error),
 }
 
 impl From<io::Error> for PcapError {
@@ -21,239 +22,623 @@ impl From<io::Error> for PcapError {
     }
 }
 
-impl From<serde_json::Error> for PcapError {
-    fn from(error: serde_json::Error) -> Self {
-        PcapError::JsonError(error)
+impl From<pcap::Error> for PcapError {
+    fn from(error: pcap::Error) -> Self {
+        PcapError::CaptureError(error)
+    }
+}
+
+/// Classic pcap global file header magic numbers (little/big endian, micro/nanosecond resolution)
+const PCAP_MAGIC_LE: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_BE: u32 = 0xd4c3b2a1;
+const PCAP_MAGIC_NS_LE: u32 = 0xa1b23c4d;
+const PCAP_MAGIC_NS_BE: u32 = 0x4d3cb2a1;
+
+/// pcapng section header block magic number
+const PCAPNG_MAGIC: u32 = 0x0a0d0d0a;
+
+/// A single captured frame together with its capture timestamp
+struct RawFrame {
+    timestamp: DateTime<Utc>,
+    data: Vec<u8>,
+}
+
+/// Parse a PCAP/PCAPNG file and return a vector of NetworkPackets
+///
+/// `filter` is an optional BPF filter expression (e.g. `"tcp port 443"`);
+/// when given, it is compiled and applied by libpcap while reading the file so
+/// that non-matching frames never make it into memory.
+pub fn parse_pcap<P: AsRef<Path>>(
+    pcap_path: P,
+    filter: Option<&str>,
+) -> Result<Vec<NetworkPacket>, PcapError> {
+    if let Some(filter) = filter {
+        return parse_pcap_filtered(pcap_path, filter);
+    }
+
+    let mut file = File::open(pcap_path)?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let frames = read_frames(&bytes)?;
+
+    Ok(frames
+        .into_iter()
+        .filter_map(|frame| parse_frame(frame).ok())
+        .collect())
+}
+
+/// Read a file through libpcap so a BPF filter can be compiled and applied during the read
+fn parse_pcap_filtered<P: AsRef<Path>>(
+    pcap_path: P,
+    filter: &str,
+) -> Result<Vec<NetworkPacket>, PcapError> {
+    let mut capture = pcap::Capture::from_file(pcap_path)?;
+    capture.filter(filter, true)?;
+
+    let mut packets = Vec::new();
+    loop {
+        match capture.next_packet() {
+            Ok(packet) => {
+                let timestamp = DateTime::<Utc>::from_timestamp(
+                    packet.header.ts.tv_sec,
+                    (packet.header.ts.tv_usec as u32).saturating_mul(1_000),
+                )
+                .unwrap_or_default();
+                let frame = RawFrame { timestamp, data: packet.data.to_vec() };
+                if let Ok(parsed) = parse_frame(frame) {
+                    packets.push(parsed);
+                }
+            }
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => return Err(PcapError::CaptureError(e)),
+        }
+    }
+
+    Ok(packets)
+}
+
+/// A live capture handle that yields parsed packets as they arrive on the wire
+pub struct LiveCapture {
+    capture: pcap::Capture<pcap::Active>,
+    remaining: Option<usize>,
+}
+
+impl LiveCapture {
+    fn new(capture: pcap::Capture<pcap::Active>, count: Option<usize>) -> Self {
+        LiveCapture { capture, remaining: count }
+    }
+}
+
+impl Iterator for LiveCapture {
+    type Item = Result<NetworkPacket, PcapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+
+        match self.capture.next_packet() {
+            Ok(packet) => {
+                if let Some(remaining) = self.remaining.as_mut() {
+                    *remaining -= 1;
+                }
+                let timestamp = DateTime::<Utc>::from_timestamp(
+                    packet.header.ts.tv_sec,
+                    (packet.header.ts.tv_usec as u32).saturating_mul(1_000),
+                )
+                .unwrap_or_default();
+                let frame = RawFrame { timestamp, data: packet.data.to_vec() };
+                Some(parse_frame(frame))
+            }
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(e) => Some(Err(PcapError::CaptureError(e))),
+        }
+    }
+}
+
+/// Open a live capture on the named network interface
+///
+/// Stops after `count` captured frames if given, otherwise captures until libpcap
+/// reports no more packets. Frames that fail to parse are yielded as `Err` and count
+/// toward `count`; capture errors are yielded as `Err` without ending the iteration.
+/// Packets are parsed with the same pipeline as `parse_pcap`,
+/// using the timestamp recorded by the pcap header at capture time. `filter` is an
+/// optional BPF expression compiled and applied by libpcap before packets reach us.
+pub fn capture_live(
+    device: &str,
+    count: Option<usize>,
+    filter: Option<&str>,
+) -> Result<LiveCapture, PcapError> {
+    let mut capture = pcap::Capture::from_device(device)?.promisc(true).open()?;
+    if let Some(filter) = filter {
+        capture.filter(filter, true)?;
+    }
+    Ok(LiveCapture::new(capture, count))
+}
+
+/// Open a live capture on the system's default network device, as reported by `pcap::Device::lookup`
+pub fn capture_on_default_device(
+    count: Option<usize>,
+    filter: Option<&str>,
+) -> Result<LiveCapture, PcapError> {
+    let device = pcap::Device::lookup()?
+        .ok_or_else(|| PcapError::InvalidFormat("no default capture device found".to_string()))?;
+    capture_live(&device.name, count, filter)
+}
+
+/// Dispatch to the right container-format reader based on the leading magic number
+fn read_frames(bytes: &[u8]) -> Result<Vec<RawFrame>, PcapError> {
+    if bytes.len() < 4 {
+        return Err(PcapError::InvalidFormat("file too short".to_string()));
+    }
+
+    let magic = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    match magic {
+        PCAP_MAGIC_LE | PCAP_MAGIC_BE | PCAP_MAGIC_NS_LE | PCAP_MAGIC_NS_BE => {
+            read_classic_pcap(bytes, magic)
+        }
+        PCAPNG_MAGIC => read_pcapng(bytes),
+        other => Err(PcapError::InvalidFormat(format!(
+            "unrecognized magic number: {:#010x}",
+            other
+        ))),
     }
 }
 
-/// Parse a PCAP file using tshark and return a vector of NetworkPackets
-pub fn parse_pcap<P: AsRef<Path>>(pcap_path: P) -> Result<Vec<NetworkPacket>, PcapError> {
-    // Check if tshark is available
-    if !is_tshark_installed() {
-        return Err(PcapError::TsharkNotFound);
+/// Read a classic (libpcap) capture file into raw frames
+fn read_classic_pcap(bytes: &[u8], magic: u32) -> Result<Vec<RawFrame>, PcapError> {
+    let big_endian = matches!(magic, PCAP_MAGIC_BE | PCAP_MAGIC_NS_BE);
+    let nanosecond_resolution = matches!(magic, PCAP_MAGIC_NS_LE | PCAP_MAGIC_NS_BE);
+
+    if bytes.len() < 24 {
+        return Err(PcapError::InvalidFormat("truncated global header".to_string()));
     }
 
-    // Run tshark command to convert pcap to JSON
-    let output = Command::new("tshark")
-        .args([
-            "-r", pcap_path.as_ref().to_str().unwrap(),
-            "-T", "json",
-            "-x",  // Include hex dump
-            // Fields we want to capture
-            "-e", "frame.time_epoch",
-            "-e", "eth.src",
-            "-e", "eth.dst",
-            "-e", "eth.type",
-            "-e", "ip.src",
-            "-e", "ip.dst",
-            "-e", "ip.proto",
-            "-e", "tcp.srcport",
-            "-e", "tcp.dstport",
-            "-e", "tcp.seq",
-            "-e", "tcp.ack",
-            "-e", "tcp.flags",
-            "-e", "tcp.window_size",
-            "-e", "tcp.options",
-            "-J", "tcp",  // Only TCP packets
-        ])
-        .output()?;
-
-    if !output.status.success() {
-        println!("Error running tshark: {}", String::from_utf8_lossy(&output.stderr).to_string());
-        return Err(PcapError::ParseError(
-            String::from_utf8_lossy(&output.stderr).to_string()
-        ));
+    let mut frames = Vec::new();
+    let mut offset = 24; // global header is always 24 bytes
+
+    while offset + 16 <= bytes.len() {
+        let ts_sec = read_u32(bytes, offset, big_endian);
+        let ts_frac = read_u32(bytes, offset + 4, big_endian);
+        let incl_len = read_u32(bytes, offset + 8, big_endian) as usize;
+        offset += 16;
+
+        if offset + incl_len > bytes.len() {
+            return Err(PcapError::ParseError("truncated packet record".to_string()));
+        }
+
+        let nsecs = if nanosecond_resolution {
+            ts_frac
+        } else {
+            ts_frac.saturating_mul(1_000)
+        };
+        let timestamp = DateTime::<Utc>::from_timestamp(ts_sec as i64, nsecs).unwrap_or_default();
+
+        frames.push(RawFrame {
+            timestamp,
+            data: bytes[offset..offset + incl_len].to_vec(),
+        });
+        offset += incl_len;
     }
 
-    let json_str = String::from_utf8_lossy(&output.stdout);
-    let packets: Vec<Value> = serde_json::from_str(&json_str)?;
+    Ok(frames)
+}
+
+/// Read a pcapng capture file into raw frames, following Enhanced Packet Blocks
+///
+/// Only the block types needed to recover frame bytes and timestamps are handled;
+/// interface description / name-resolution / statistics blocks are skipped.
+fn read_pcapng(bytes: &[u8]) -> Result<Vec<RawFrame>, PcapError> {
+    const ENHANCED_PACKET_BLOCK: u32 = 0x00000006;
+    const SIMPLE_PACKET_BLOCK: u32 = 0x00000003;
+
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    let mut big_endian = false;
+
+    while offset + 12 <= bytes.len() {
+        let block_type = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let block_total_length = if block_type == PCAPNG_MAGIC {
+            // Section header block carries its own byte-order magic right after the length field
+            let byte_order_magic =
+                u32::from_le_bytes(bytes[offset + 8..offset + 12].try_into().unwrap());
+            big_endian = byte_order_magic != 0x1a2b3c4d;
+            read_u32(bytes, offset + 4, big_endian)
+        } else {
+            read_u32(bytes, offset + 4, big_endian)
+        } as usize;
+
+        // Minimum block size is 12 bytes (type + length + trailing length) in general,
+        // but Enhanced/Simple Packet Blocks have additional fixed fields we read below
+        // that must also fit before we touch them.
+        const ENHANCED_PACKET_BLOCK_MIN_LEN: usize = 32;
+        const SIMPLE_PACKET_BLOCK_MIN_LEN: usize = 16;
+        let min_block_len = match block_type {
+            ENHANCED_PACKET_BLOCK => ENHANCED_PACKET_BLOCK_MIN_LEN,
+            SIMPLE_PACKET_BLOCK => SIMPLE_PACKET_BLOCK_MIN_LEN,
+            _ => 12,
+        };
+
+        if block_total_length < min_block_len || offset + block_total_length > bytes.len() {
+            return Err(PcapError::ParseError("truncated pcapng block".to_string()));
+        }
+
+        if block_type == ENHANCED_PACKET_BLOCK {
+            let ts_high = read_u32(bytes, offset + 12, big_endian);
+            let ts_low = read_u32(bytes, offset + 16, big_endian);
+            let captured_len = read_u32(bytes, offset + 20, big_endian) as usize;
+            let data_start = offset + 28;
+
+            if data_start + captured_len > bytes.len() {
+                return Err(PcapError::ParseError("truncated packet data".to_string()));
+            }
 
-    // Parse JSON into NetworkPacket structs
-    let network_packets = packets.into_iter()
-        .filter_map(|packet| parse_packet_json(packet).ok())
-        .collect();
+            // Interface timestamp resolution defaults to microseconds unless an
+            // interface description block says otherwise; we assume the common default.
+            let ticks = ((ts_high as u64) << 32) | ts_low as u64;
+            let timestamp = DateTime::<Utc>::from_timestamp(
+                (ticks / 1_000_000) as i64,
+                ((ticks % 1_000_000) * 1_000) as u32,
+            )
+            .unwrap_or_default();
 
-    Ok(network_packets)
+            frames.push(RawFrame {
+                timestamp,
+                data: bytes[data_start..data_start + captured_len].to_vec(),
+            });
+        } else if block_type == SIMPLE_PACKET_BLOCK {
+            let captured_len = read_u32(bytes, offset + 8, big_endian) as usize;
+            let data_start = offset + 12;
+
+            if data_start + captured_len > bytes.len() {
+                return Err(PcapError::ParseError("truncated packet data".to_string()));
+            }
+
+            frames.push(RawFrame {
+                timestamp: Utc::now(),
+                data: bytes[data_start..data_start + captured_len].to_vec(),
+            });
+        }
+
+        offset += block_total_length;
+    }
+
+    Ok(frames)
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let word: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+    if big_endian {
+        u32::from_be_bytes(word)
+    } else {
+        u32::from_le_bytes(word)
+    }
 }
 
-fn parse_packet_json(json: Value) -> Result<NetworkPacket, PcapError> {
-    let layers = json.get("_source")
-        .and_then(|src| src.get("layers"))
-        .ok_or_else(|| PcapError::ParseError("Invalid JSON structure".to_string()))?;
-
-    // Parse timestamp - get first element from array
-    let timestamp = layers.get("frame.time_epoch")
-        .and_then(|t| t.as_array())
-        .and_then(|arr| arr.first())
-        .and_then(|t| t.as_str())
-        .and_then(|t| t.parse::<f64>().ok())
-        .map(|t| {
-            let secs = t.trunc() as i64;
-            let nsecs = (t.fract() * 1_000_000_000.0) as u32;
-            DateTime::<Utc>::from_timestamp(secs, nsecs)
-                .unwrap_or_default()
-        })
-        .ok_or_else(|| PcapError::ParseError("Invalid timestamp".to_string()))?;
-
-    // Parse other layers
-    let ethernet_layer = parse_ethernet_layer(layers)?;
-    let ip_layer = parse_ip_layer(layers)?;
-    let tcp_layer = parse_tcp_layer(layers)?;
-    let application_layer = parse_application_layer(layers)?;
+/// Ethertype for ARP frames, routed to `PacketBody::Arp` instead of the IP pipeline
+const ETHERTYPE_ARP: u16 = 0x0806;
+
+/// Slice a raw Ethernet frame with etherparse and map it onto our packet model
+fn parse_frame(frame: RawFrame) -> Result<NetworkPacket, PcapError> {
+    let sliced = SlicedPacket::from_ethernet(&frame.data)
+        .map_err(|e| PcapError::ParseError(e.to_string()))?;
+
+    let ethernet_layer = parse_ethernet_layer(&sliced)?;
+
+    let body = if ethernet_layer.ethertype == ETHERTYPE_ARP {
+        PacketBody::Arp(parse_arp_packet(sliced.payload)?)
+    } else {
+        let ip_layer = parse_ip_layer(&sliced)?;
+        let transport_layer = parse_transport_layer(&sliced)?;
+        let application_layer = parse_application_layer(&sliced, &transport_layer)?;
+        PacketBody::Ip { ip_layer, transport_layer, application_layer }
+    };
 
     Ok(NetworkPacket {
-        timestamp,
+        timestamp: frame.timestamp,
         ethernet_layer,
-        ip_layer,
-        tcp_layer,
-        application_layer,
+        body,
     })
 }
 
-fn parse_ethernet_layer(layers: &Value) -> Result<EthernetFrame, PcapError> {
-    Ok(EthernetFrame {
-        source_mac: parse_mac_address(layers.get("eth.src")
-            .and_then(|mac| mac.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|mac| mac.as_str())
-            .ok_or_else(|| PcapError::ParseError("Invalid source MAC".to_string()))?),
-        destination_mac: parse_mac_address(layers.get("eth.dst")
-            .and_then(|mac| mac.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|mac| mac.as_str())
-            .ok_or_else(|| PcapError::ParseError("Invalid destination MAC".to_string()))?),
-        ethertype: layers.get("eth.type")
-            .and_then(|t| t.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|t| t.as_str())
-            .and_then(|t| u16::from_str_radix(t, 16).ok())
-            .unwrap_or(0x0800),
-        frame_check_sequence: 0,
-    })
-}
+/// Parse an Ethernet/IPv4 ARP packet (the only hardware/protocol combination in practice)
+fn parse_arp_packet(payload: &[u8]) -> Result<ArpPacket, PcapError> {
+    if payload.len() < 28 {
+        return Err(PcapError::ParseError("truncated ARP packet".to_string()));
+    }
 
-fn parse_ip_layer(layers: &Value) -> Result<IPv4Packet, PcapError> {
-    Ok(IPv4Packet {
-        version: 4,
-        ihl: 5,
-        dscp: 0,
-        ecn: 0,
-        total_length: 0,
-        identification: 0,
-        flags: IPv4Flags {
-            reserved: false,
-            dont_fragment: false,
-            more_fragments: false,
-        },
-        fragment_offset: 0,
-        ttl: 64,
-        protocol: 6,
-        header_checksum: 0,
-        source_ip: parse_ip_address(layers.get("ip.src")
-            .and_then(|ip| ip.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|ip| ip.as_str())
-            .ok_or_else(|| PcapError::ParseError("Invalid source IP".to_string()))?),
-        destination_ip: parse_ip_address(layers.get("ip.dst")
-            .and_then(|ip| ip.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|ip| ip.as_str())
-            .ok_or_else(|| PcapError::ParseError("Invalid destination IP".to_string()))?),
-        options: Vec::new(),
+    let operation = match u16::from_be_bytes([payload[6], payload[7]]) {
+        1 => ArpOperation::Request,
+        2 => ArpOperation::Reply,
+        other => ArpOperation::Other(other),
+    };
+
+    Ok(ArpPacket {
+        hardware_type: u16::from_be_bytes([payload[0], payload[1]]),
+        protocol_type: u16::from_be_bytes([payload[2], payload[3]]),
+        hardware_addr_len: payload[4],
+        protocol_addr_len: payload[5],
+        operation,
+        sender_hardware_addr: payload[8..14].try_into().unwrap(),
+        sender_protocol_addr: payload[14..18].try_into().unwrap(),
+        target_hardware_addr: payload[18..24].try_into().unwrap(),
+        target_protocol_addr: payload[24..28].try_into().unwrap(),
     })
 }
 
-fn parse_tcp_layer(layers: &Value) -> Result<TCPSegment, PcapError> {
-    let flags = parse_tcp_flags(layers.get("tcp.flags")
-        .and_then(|f| f.as_array())
-        .and_then(|arr| arr.first())
-        .and_then(|f| f.as_str())
-        .unwrap_or("0x000"));
-
-    Ok(TCPSegment {
-        source_port: layers.get("tcp.srcport")
-            .and_then(|p| p.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|p| p.as_str())
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(0),
-        destination_port: layers.get("tcp.dstport")
-            .and_then(|p| p.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|p| p.as_str())
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(0),
-        sequence_number: layers.get("tcp.seq")
-            .and_then(|s| s.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|s| s.as_str())
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(0),
-        acknowledgment_number: layers.get("tcp.ack")
-            .and_then(|a| a.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|a| a.as_str())
-            .and_then(|a| a.parse().ok())
-            .unwrap_or(0),
-        data_offset: 5,
-        flags,
-        window_size: layers.get("tcp.window_size")
-            .and_then(|w| w.as_array())
-            .and_then(|arr| arr.first())
-            .and_then(|w| w.as_str())
-            .and_then(|w| w.parse().ok())
-            .unwrap_or(0),
-        checksum: 0,
-        urgent_pointer: 0,
-        options: Vec::new(),
-    })
+fn parse_ethernet_layer(sliced: &SlicedPacket) -> Result<EthernetFrame, PcapError> {
+    match &sliced.link {
+        Some(LinkSlice::Ethernet2(eth)) => Ok(EthernetFrame {
+            source_mac: eth.source(),
+            destination_mac: eth.destination(),
+            ethertype: eth.ether_type(),
+            frame_check_sequence: 0,
+        }),
+        _ => Err(PcapError::ParseError("no Ethernet II link layer".to_string())),
+    }
 }
 
-/// Helper function to parse MAC address string into bytes
-fn parse_mac_address(mac_str: &str) -> [u8; 6] {
-    let mut mac = [0u8; 6];
-    let parts: Vec<&str> = mac_str.split(':').collect();
-    for (i, part) in parts.iter().enumerate() {
-        if i < 6 {
-            mac[i] = u8::from_str_radix(part, 16).unwrap_or(0);
+fn parse_ip_layer(sliced: &SlicedPacket) -> Result<IpLayer, PcapError> {
+    match &sliced.ip {
+        Some(InternetSlice::Ipv4(header_slice, _extensions)) => {
+            let options = header_slice.options().to_vec();
+            let header = header_slice.to_header();
+            Ok(IpLayer::V4(IPv4Packet {
+                version: 4,
+                ihl: header.ihl(),
+                dscp: header.differentiated_services_code_point,
+                ecn: header.explicit_congestion_notification,
+                total_length: header.total_len(),
+                identification: header.identification,
+                flags: IPv4Flags {
+                    reserved: false,
+                    dont_fragment: header.dont_fragment,
+                    more_fragments: header.more_fragments,
+                },
+                fragment_offset: header.fragments_offset,
+                ttl: header.time_to_live,
+                protocol: header.protocol,
+                header_checksum: header.header_checksum,
+                source_ip: header.source,
+                destination_ip: header.destination,
+                options,
+            }))
+        }
+        Some(InternetSlice::Ipv6(header, _)) => {
+            let header = header.to_header();
+            Ok(IpLayer::V6(IPv6Packet {
+                traffic_class: header.traffic_class,
+                flow_label: header.flow_label,
+                payload_length: header.payload_length,
+                next_header: header.next_header,
+                hop_limit: header.hop_limit,
+                source_ip: header.source,
+                destination_ip: header.destination,
+            }))
         }
+        _ => Err(PcapError::ParseError("no IPv4/IPv6 internet layer".to_string())),
+    }
+}
+
+fn parse_transport_layer(sliced: &SlicedPacket) -> Result<TransportLayer, PcapError> {
+    match &sliced.transport {
+        Some(TransportSlice::Tcp(_)) => Ok(TransportLayer::Tcp(parse_tcp_segment(sliced)?)),
+        Some(TransportSlice::Udp(udp)) => Ok(TransportLayer::Udp(UdpDatagram {
+            source_port: udp.source_port(),
+            destination_port: udp.destination_port(),
+            length: udp.length(),
+            checksum: udp.checksum(),
+        })),
+        _ => Err(PcapError::ParseError("no TCP/UDP transport layer".to_string())),
     }
-    mac
 }
 
-/// Helper function to parse IP address string into bytes
-fn parse_ip_address(ip_str: &str) -> [u8; 4] {
-    let mut ip = [0u8; 4];
-    let parts: Vec<&str> = ip_str.split('.').collect();
-    for (i, part) in parts.iter().enumerate() {
-        if i < 4 {
-            ip[i] = part.parse().unwrap_or(0);
+fn parse_tcp_segment(sliced: &SlicedPacket) -> Result<TCPSegment, PcapError> {
+    match &sliced.transport {
+        Some(TransportSlice::Tcp(tcp)) => Ok(TCPSegment {
+            source_port: tcp.source_port(),
+            destination_port: tcp.destination_port(),
+            sequence_number: tcp.sequence_number(),
+            acknowledgment_number: tcp.acknowledgment_number(),
+            data_offset: tcp.data_offset(),
+            flags: TCPFlags {
+                fin: tcp.fin(),
+                syn: tcp.syn(),
+                rst: tcp.rst(),
+                psh: tcp.psh(),
+                ack: tcp.ack(),
+                urg: tcp.urg(),
+                ece: tcp.ece(),
+                cwr: tcp.cwr(),
+            },
+            window_size: tcp.window_size(),
+            checksum: tcp.checksum(),
+            urgent_pointer: tcp.urgent_pointer(),
+            options: tcp
+                .options_iterator()
+                .filter_map(|opt| opt.ok())
+                .map(parse_tcp_option)
+                .collect(),
+        }),
+        _ => Err(PcapError::ParseError("no TCP transport layer".to_string())),
+    }
+}
+
+fn parse_tcp_option(option: etherparse::TcpOptionElement) -> TCPOption {
+    use etherparse::TcpOptionElement::*;
+    match option {
+        Noop => TCPOption { kind: 1, length: 1, data: Vec::new() },
+        MaximumSegmentSize(mss) => TCPOption {
+            kind: 2,
+            length: 4,
+            data: mss.to_be_bytes().to_vec(),
+        },
+        WindowScale(shift) => TCPOption {
+            kind: 3,
+            length: 3,
+            data: vec![shift],
+        },
+        SelectiveAcknowledgementPermitted => TCPOption {
+            kind: 4,
+            length: 2,
+            data: Vec::new(),
+        },
+        SelectiveAcknowledgement(first, rest) => {
+            let mut data = Vec::new();
+            data.extend_from_slice(&first.0.to_be_bytes());
+            data.extend_from_slice(&first.1.to_be_bytes());
+            for block in rest.iter().filter_map(|b| *b) {
+                data.extend_from_slice(&block.0.to_be_bytes());
+                data.extend_from_slice(&block.1.to_be_bytes());
+            }
+            TCPOption {
+                kind: 5,
+                length: 2 + data.len() as u8,
+                data,
+            }
+        }
+        Timestamp(ts_val, ts_echo) => {
+            let mut data = Vec::new();
+            data.extend_from_slice(&ts_val.to_be_bytes());
+            data.extend_from_slice(&ts_echo.to_be_bytes());
+            TCPOption { kind: 8, length: 10, data }
         }
     }
-    ip
 }
 
-/// Helper function to parse TCP flags
-fn parse_tcp_flags(flags_str: &str) -> TCPFlags {
-    let flags_value = u16::from_str_radix(&flags_str.trim_start_matches("0x"), 16).unwrap_or(0);
-    
-    TCPFlags {
-        fin: flags_value & 0x001 != 0,
-        syn: flags_value & 0x002 != 0,
-        rst: flags_value & 0x004 != 0,
-        psh: flags_value & 0x008 != 0,
-        ack: flags_value & 0x010 != 0,
-        urg: flags_value & 0x020 != 0,
-        ece: flags_value & 0x040 != 0,
-        cwr: flags_value & 0x080 != 0,
+fn parse_application_layer(
+    sliced: &SlicedPacket,
+    transport_layer: &TransportLayer,
+) -> Result<ApplicationData, PcapError> {
+    let payload = sliced.payload.to_vec();
+    let (source_port, destination_port) = match transport_layer {
+        TransportLayer::Tcp(tcp) => (tcp.source_port, tcp.destination_port),
+        TransportLayer::Udp(udp) => (udp.source_port, udp.destination_port),
+    };
+    let protocol = classify_protocol(source_port, destination_port);
+
+    Ok(ApplicationData { protocol, payload })
+}
+
+/// Guess the application protocol from well-known TCP/UDP ports
+fn classify_protocol(source_port: u16, destination_port: u16) -> ApplicationProtocol {
+    match (source_port, destination_port) {
+        (53, _) | (_, 53) => ApplicationProtocol::DNS,
+        (80, _) | (_, 80) => ApplicationProtocol::HTTP,
+        (443, _) | (_, 443) => ApplicationProtocol::HTTPS,
+        (21, _) | (_, 21) => ApplicationProtocol::FTP,
+        (22, _) | (_, 22) => ApplicationProtocol::SSH,
+        (25, _) | (_, 25) => ApplicationProtocol::SMTP,
+        _ => ApplicationProtocol::Custom("unknown".to_string()),
     }
 }
 
-/// Check if tshark is installed
-fn is_tshark_installed() -> bool {
-    Command::new("tshark")
-        .arg("--version")
-        .output()
-        .is_ok()
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use etherparse::PacketBuilder;
+
+    const SRC_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 1];
+    const DST_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 2];
+
+    fn raw_frame(data: Vec<u8>) -> RawFrame {
+        RawFrame { timestamp: Utc::now(), data }
+    }
+
+    #[test]
+    fn parse_frame_reads_ipv4_tcp_packet() {
+        let builder = PacketBuilder::ethernet2(SRC_MAC, DST_MAC)
+            .ipv4([10, 0, 0, 1], [10, 0, 0, 2], 64)
+            .tcp(4000, 80, 1000, 65535)
+            .syn();
+        let payload = b"hello".to_vec();
+        let mut data = Vec::new();
+        builder.write(&mut data, &payload).unwrap();
+
+        let packet = parse_frame(raw_frame(data)).unwrap();
+
+        assert_eq!(packet.ethernet_layer.source_mac, SRC_MAC);
+        assert_eq!(packet.ethernet_layer.destination_mac, DST_MAC);
+
+        let (ip_layer, transport_layer, application_layer) = packet.body.as_ip().unwrap();
+        match ip_layer {
+            IpLayer::V4(ipv4) => {
+                assert_eq!(ipv4.source_ip, [10, 0, 0, 1]);
+                assert_eq!(ipv4.destination_ip, [10, 0, 0, 2]);
+                assert_eq!(ipv4.ttl, 64);
+            }
+            IpLayer::V6(_) => panic!("expected an IPv4 layer"),
+        }
+        match transport_layer {
+            TransportLayer::Tcp(tcp) => {
+                assert_eq!(tcp.source_port, 4000);
+                assert_eq!(tcp.destination_port, 80);
+                assert_eq!(tcp.sequence_number, 1000);
+                assert!(tcp.flags.syn);
+            }
+            TransportLayer::Udp(_) => panic!("expected a TCP segment"),
+        }
+        assert_eq!(application_layer.payload, payload);
+    }
+
+    #[test]
+    fn parse_frame_reads_ipv6_udp_packet() {
+        let source = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let destination = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+        let builder = PacketBuilder::ethernet2(SRC_MAC, DST_MAC)
+            .ipv6(source, destination, 64)
+            .udp(5353, 53);
+        let payload = b"query".to_vec();
+        let mut data = Vec::new();
+        builder.write(&mut data, &payload).unwrap();
+
+        let packet = parse_frame(raw_frame(data)).unwrap();
+
+        let (ip_layer, transport_layer, application_layer) = packet.body.as_ip().unwrap();
+        match ip_layer {
+            IpLayer::V6(ipv6) => {
+                assert_eq!(ipv6.source_ip, source);
+                assert_eq!(ipv6.destination_ip, destination);
+                assert_eq!(ipv6.hop_limit, 64);
+            }
+            IpLayer::V4(_) => panic!("expected an IPv6 layer"),
+        }
+        match transport_layer {
+            TransportLayer::Udp(udp) => {
+                assert_eq!(udp.source_port, 5353);
+                assert_eq!(udp.destination_port, 53);
+            }
+            TransportLayer::Tcp(_) => panic!("expected a UDP datagram"),
+        }
+        assert_eq!(application_layer.payload, payload);
+        assert!(matches!(application_layer.protocol, ApplicationProtocol::DNS));
+    }
+
+    #[test]
+    fn parse_arp_packet_reads_request_fields() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&1u16.to_be_bytes()); // hardware type: Ethernet
+        payload.extend_from_slice(&0x0800u16.to_be_bytes()); // protocol type: IPv4
+        payload.push(6); // hardware address length
+        payload.push(4); // protocol address length
+        payload.extend_from_slice(&1u16.to_be_bytes()); // operation: request
+        payload.extend_from_slice(&[0x02, 0, 0, 0, 0, 1]); // sender MAC
+        payload.extend_from_slice(&[10, 0, 0, 1]); // sender IP
+        payload.extend_from_slice(&[0, 0, 0, 0, 0, 0]); // target MAC (unknown for a request)
+        payload.extend_from_slice(&[10, 0, 0, 2]); // target IP
+
+        let arp = parse_arp_packet(&payload).unwrap();
+
+        assert!(matches!(arp.operation, ArpOperation::Request));
+        assert_eq!(arp.sender_hardware_addr, [0x02, 0, 0, 0, 0, 1]);
+        assert_eq!(arp.sender_protocol_addr, [10, 0, 0, 1]);
+        assert_eq!(arp.target_protocol_addr, [10, 0, 0, 2]);
+    }
+
+    #[test]
+    fn parse_arp_packet_rejects_truncated_payload() {
+        let payload = vec![0u8; 27];
+        assert!(matches!(parse_arp_packet(&payload), Err(PcapError::ParseError(_))));
+    }
+}
@@ -1,65 +1,309 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
 use serde::Serialize;
 use chrono::DateTime;
 use chrono::Utc;
-use crate::NetworkPacket;
+use crate::{build_arp_table, NetworkPacket, TCPSegment, TransportLayer};
 
-/// Represents a TCP session
+/// One side of a TCP flow, identified by IP and port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Endpoint {
+    pub ip: IpAddr,
+    pub port: u16,
+}
+
+/// A flow endpoint labeled with the MAC address observed for it, if any
+///
+/// The MAC is filled in from traffic on the flow itself, falling back to the
+/// IP -> MAC table learned from ARP (see [`build_arp_table`]) for hosts whose
+/// own packets we didn't see send from that side.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionEndpoint {
+    pub ip: IpAddr,
+    pub port: u16,
+    pub mac: Option<[u8; 6]>,
+}
+
+/// Lifecycle state of a tracked TCP flow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FlowState {
+    Syn,
+    SynAck,
+    Established,
+    FinWait,
+    Closed,
+}
+
+/// Represents a TCP session, reassembled from both directions of one flow
 #[derive(Debug, Serialize)]
 pub struct TCPSession {
-    source_port: u16,
-    destination_port: u16,
-    source_ip: [u8; 4],
-    destination_ip: [u8; 4],
+    pub endpoint_a: SessionEndpoint,
+    pub endpoint_b: SessionEndpoint,
+    pub state: FlowState,
+    pub start_timestamp: DateTime<Utc>,
+    pub end_timestamp: Option<DateTime<Utc>>,
+    pub packets: Vec<NetworkPacket>,
+    /// Reassembled application-layer bytes sent from `endpoint_a` to `endpoint_b`
+    pub stream_a_to_b: Vec<u8>,
+    /// Reassembled application-layer bytes sent from `endpoint_b` to `endpoint_a`
+    pub stream_b_to_a: Vec<u8>,
+}
+
+/// Represents a UDP flow, grouped by the bidirectional 4-tuple
+#[derive(Debug, Serialize)]
+pub struct UdpFlow {
+    pub source_port: u16,
+    pub destination_port: u16,
+    pub source_ip: IpAddr,
+    pub destination_ip: IpAddr,
+    pub start_timestamp: DateTime<Utc>,
+    pub end_timestamp: DateTime<Utc>,
+    pub packets: Vec<NetworkPacket>,
+}
+
+/// Per-flow mutable state accumulated while walking the packet list
+struct FlowBuilder {
+    endpoint_a: Endpoint,
+    endpoint_b: Endpoint,
+    state: FlowState,
     start_timestamp: DateTime<Utc>,
     end_timestamp: Option<DateTime<Utc>>,
     packets: Vec<NetworkPacket>,
+    initial_seq_a: Option<u32>,
+    initial_seq_b: Option<u32>,
+    /// Sequence number of the FIN byte most recently sent in this direction (the byte
+    /// right after any payload), used to tell a FIN that merely acks prior data apart
+    /// from the FIN+ACK (or later ACK) that actually acknowledges this FIN.
+    fin_seq_a: Option<u32>,
+    fin_seq_b: Option<u32>,
+    mac_a: Option<[u8; 6]>,
+    mac_b: Option<[u8; 6]>,
+    segments_a_to_b: BTreeMap<u32, Vec<u8>>,
+    segments_b_to_a: BTreeMap<u32, Vec<u8>>,
+    seen_a_to_b: HashSet<(u32, usize)>,
+    seen_b_to_a: HashSet<(u32, usize)>,
 }
 
-pub fn find_tcp_sessions(packets: &[NetworkPacket]) -> Vec<TCPSession> {
-    let mut sessions: HashMap<(u16, u16, [u8; 4], [u8; 4]), (DateTime<Utc>, Option<DateTime<Utc>>, Vec<NetworkPacket>)> = HashMap::new();
+impl FlowBuilder {
+    fn new(endpoint_a: Endpoint, endpoint_b: Endpoint, start_timestamp: DateTime<Utc>) -> Self {
+        FlowBuilder {
+            endpoint_a,
+            endpoint_b,
+            state: FlowState::Syn,
+            start_timestamp,
+            end_timestamp: None,
+            packets: Vec::new(),
+            initial_seq_a: None,
+            initial_seq_b: None,
+            fin_seq_a: None,
+            fin_seq_b: None,
+            mac_a: None,
+            mac_b: None,
+            segments_a_to_b: BTreeMap::new(),
+            segments_b_to_a: BTreeMap::new(),
+            seen_a_to_b: HashSet::new(),
+            seen_b_to_a: HashSet::new(),
+        }
+    }
 
-    for packet in packets {
-        if packet.tcp_layer.flags.syn {
-            // Start of a new session
-            let key = (
-                packet.tcp_layer.source_port,
-                packet.tcp_layer.destination_port,
-                packet.ip_layer.source_ip,
-                packet.ip_layer.destination_ip,
-            );
-            sessions
-                .entry(key)
-                .or_insert((packet.timestamp, None, vec![]))
-                .2
-                .push(packet.clone());
+    fn ingest(&mut self, packet: &NetworkPacket, tcp: &TCPSegment, a_to_b: bool) {
+        self.packets.push(packet.clone());
+
+        // Latch the first sequence number we see in this direction as the baseline for
+        // relative offsets, whether or not it's the SYN — a capture that starts mid-flow
+        // never has a SYN to anchor on, and re-deriving the baseline from every packet's
+        // own sequence number (as this used to) collapsed every payload onto offset 0.
+        let initial_seq = if a_to_b { &mut self.initial_seq_a } else { &mut self.initial_seq_b };
+        initial_seq.get_or_insert(tcp.sequence_number);
+
+        let mac = if a_to_b { &mut self.mac_a } else { &mut self.mac_b };
+        mac.get_or_insert(packet.ethernet_layer.source_mac);
+
+        let payload = packet.body.as_ip().map(|(_, _, application_layer)| &application_layer.payload);
+        self.advance_state(tcp, a_to_b, payload.map_or(0, |p| p.len()), packet.timestamp);
+
+        let Some(payload) = payload else { return };
+        if payload.is_empty() {
+            // Pure ACKs (and empty SYN/FIN control segments) carry no stream bytes
+            return;
+        }
+
+        // Always set above, for this same direction, before we reach this point
+        let initial_seq = if a_to_b { self.initial_seq_a } else { self.initial_seq_b }
+            .expect("initial sequence number latched before stream reassembly");
+        let relative_seq = tcp.sequence_number.wrapping_sub(initial_seq);
+
+        let seen = if a_to_b { &mut self.seen_a_to_b } else { &mut self.seen_b_to_a };
+        if !seen.insert((relative_seq, payload.len())) {
+            // Retransmission of a segment we already have
+            return;
+        }
+
+        let segments = if a_to_b { &mut self.segments_a_to_b } else { &mut self.segments_b_to_a };
+        segments.entry(relative_seq).or_insert_with(|| payload.clone());
+    }
+
+    /// Advance flow lifecycle state for one observed segment
+    ///
+    /// `payload_len` is the application-layer byte count carried by this segment,
+    /// needed to place a FIN's sequence number correctly when it piggybacks data.
+    fn advance_state(&mut self, tcp: &TCPSegment, a_to_b: bool, payload_len: usize, timestamp: DateTime<Utc>) {
+        if tcp.flags.rst {
+            self.state = FlowState::Closed;
+            self.end_timestamp.get_or_insert(timestamp);
+            return;
         }
 
-        if packet.tcp_layer.flags.fin {
-            // End of an existing session
-            let key = (
-                packet.tcp_layer.destination_port,
-                packet.tcp_layer.source_port,
-                packet.ip_layer.destination_ip,
-                packet.ip_layer.source_ip,
-            );
-
-            if let Some(session) = sessions.get_mut(&key) {
-                session.1 = Some(packet.timestamp);
-                session.2.push(packet.clone());
+        // A FIN also carries ACK on every real-world stack (ACK is set on everything
+        // past the handshake), so "FIN && ACK" alone can't tell a FIN that merely acks
+        // prior data apart from the FIN+ACK that finally acknowledges the peer's own
+        // FIN. Compare against the FIN sequence number we recorded for the other side.
+        let peer_fin_seq = if a_to_b { self.fin_seq_b } else { self.fin_seq_a };
+        let acks_peer_fin = tcp.flags.ack
+            && peer_fin_seq.is_some_and(|fin_seq| tcp.acknowledgment_number == fin_seq.wrapping_add(1));
+
+        if tcp.flags.fin {
+            let fin_seq = if a_to_b { &mut self.fin_seq_a } else { &mut self.fin_seq_b };
+            *fin_seq = Some(tcp.sequence_number.wrapping_add(payload_len as u32));
+        }
+
+        if acks_peer_fin {
+            self.state = FlowState::Closed;
+            self.end_timestamp.get_or_insert(timestamp);
+        } else if tcp.flags.fin {
+            self.state = FlowState::FinWait;
+        } else if tcp.flags.syn && tcp.flags.ack {
+            self.state = FlowState::SynAck;
+        } else if tcp.flags.syn {
+            // A bare SYN opens a new flow; it shouldn't regress one that's already
+            // progressed past the handshake (e.g. a retransmitted/replayed SYN arriving
+            // after the connection is established, finishing, or closed).
+            if !matches!(
+                self.state,
+                FlowState::SynAck | FlowState::Established | FlowState::FinWait | FlowState::Closed
+            ) {
+                self.state = FlowState::Syn;
             }
+        } else if matches!(self.state, FlowState::Syn | FlowState::SynAck) {
+            self.state = FlowState::Established;
         }
+    }
 
-        // Track packets for ongoing sessions
-        for session in sessions.values_mut() {
-            session.2.push(packet.clone());
+    fn finish(self, arp_table: &HashMap<IpAddr, HashSet<[u8; 6]>>) -> TCPSession {
+        let endpoint_a = SessionEndpoint {
+            ip: self.endpoint_a.ip,
+            port: self.endpoint_a.port,
+            mac: self.mac_a.or_else(|| mac_for(arp_table, self.endpoint_a.ip)),
+        };
+        let endpoint_b = SessionEndpoint {
+            ip: self.endpoint_b.ip,
+            port: self.endpoint_b.port,
+            mac: self.mac_b.or_else(|| mac_for(arp_table, self.endpoint_b.ip)),
+        };
+
+        TCPSession {
+            endpoint_a,
+            endpoint_b,
+            state: self.state,
+            start_timestamp: self.start_timestamp,
+            end_timestamp: self.end_timestamp,
+            packets: self.packets,
+            stream_a_to_b: self.segments_a_to_b.into_values().flatten().collect(),
+            stream_b_to_a: self.segments_b_to_a.into_values().flatten().collect(),
         }
     }
+}
+
+/// Look up a host's MAC address from an ARP table built by [`build_arp_table`]
+///
+/// An IP claimed by more than one MAC (a spoofing indicator) has no single "right"
+/// answer here, so this just picks one of the observed MACs for display purposes.
+fn mac_for(arp_table: &HashMap<IpAddr, HashSet<[u8; 6]>>, ip: IpAddr) -> Option<[u8; 6]> {
+    arp_table.get(&ip).and_then(|macs| macs.iter().next().copied())
+}
+
+/// Sort a packet's two endpoints into a canonical `(lower, higher)` order so that
+/// both directions of a flow hash to the same key, and report whether this packet
+/// travelled from the lower endpoint to the higher one.
+fn canonicalize(src: Endpoint, dst: Endpoint) -> (Endpoint, Endpoint, bool) {
+    if src <= dst {
+        (src, dst, true)
+    } else {
+        (dst, src, false)
+    }
+}
+
+/// Track TCP flows across a packet capture, reassembling each direction's byte
+/// stream in sequence-number order and following the flow through its lifecycle:
+/// `Syn` -> `SynAck` -> `Established` -> `FinWait` -> `Closed`.
+pub fn find_tcp_sessions(packets: &[NetworkPacket]) -> Vec<TCPSession> {
+    let arp_table = build_arp_table(packets);
+    let mut flows: HashMap<(Endpoint, Endpoint), FlowBuilder> = HashMap::new();
+
+    for packet in packets {
+        let (ip_layer, transport_layer, _) = match packet.body.as_ip() {
+            Some(layers) => layers,
+            None => continue,
+        };
+        let tcp = match transport_layer {
+            TransportLayer::Tcp(tcp) => tcp,
+            TransportLayer::Udp(_) => continue,
+        };
+
+        let src = Endpoint { ip: ip_layer.source_addr(), port: tcp.source_port };
+        let dst = Endpoint { ip: ip_layer.destination_addr(), port: tcp.destination_port };
+        let (endpoint_a, endpoint_b, a_to_b) = canonicalize(src, dst);
 
-    // Convert to vector format
-    sessions
+        let flow = flows
+            .entry((endpoint_a, endpoint_b))
+            .or_insert_with(|| FlowBuilder::new(endpoint_a, endpoint_b, packet.timestamp));
+        flow.ingest(packet, tcp, a_to_b);
+    }
+
+    flows.into_values().map(|flow| flow.finish(&arp_table)).collect()
+}
+
+/// Bidirectional 4-tuple key for a UDP flow: the two `(port, ip)` endpoints ordered by
+/// port then ip, laid out as (lower port, higher port, lower's ip, higher's ip)
+type UdpFlowKey = (u16, u16, IpAddr, IpAddr);
+/// Accumulated state for a UDP flow while walking the packet list
+type UdpFlowAccumulator = (DateTime<Utc>, DateTime<Utc>, Vec<NetworkPacket>);
+
+/// Group UDP datagrams into flows keyed by the bidirectional 4-tuple, so that
+/// both directions of e.g. a DNS query/response pair land in the same flow.
+pub fn find_udp_flows(packets: &[NetworkPacket]) -> Vec<UdpFlow> {
+    let mut flows: HashMap<UdpFlowKey, UdpFlowAccumulator> = HashMap::new();
+
+    for packet in packets {
+        let (ip_layer, transport_layer, _) = match packet.body.as_ip() {
+            Some(layers) => layers,
+            None => continue,
+        };
+        let udp = match transport_layer {
+            TransportLayer::Udp(udp) => udp,
+            TransportLayer::Tcp(_) => continue,
+        };
+
+        let source_addr = ip_layer.source_addr();
+        let destination_addr = ip_layer.destination_addr();
+
+        // Normalize so both directions of the flow share one key
+        let key = if (udp.source_port, source_addr) <= (udp.destination_port, destination_addr) {
+            (udp.source_port, udp.destination_port, source_addr, destination_addr)
+        } else {
+            (udp.destination_port, udp.source_port, destination_addr, source_addr)
+        };
+
+        let entry = flows
+            .entry(key)
+            .or_insert((packet.timestamp, packet.timestamp, vec![]));
+        entry.1 = packet.timestamp;
+        entry.2.push(packet.clone());
+    }
+
+    flows
         .into_iter()
-        .map(|(key, (start, end, packets))| TCPSession {
+        .map(|(key, (start, end, packets))| UdpFlow {
             source_port: key.0,
             destination_port: key.1,
             source_ip: key.2,
@@ -69,4 +313,203 @@ pub fn find_tcp_sessions(packets: &[NetworkPacket]) -> Vec<TCPSession> {
             packets,
         })
         .collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ApplicationData, ApplicationProtocol, EthernetFrame, IPv4Flags, IPv4Packet, IpLayer,
+        PacketBody, TCPFlags, UdpDatagram,
+    };
+    use chrono::TimeZone;
+
+    fn timestamp(offset_secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + offset_secs, 0).unwrap()
+    }
+
+    fn tcp_packet(
+        source: ([u8; 4], u16),
+        destination: ([u8; 4], u16),
+        sequence_number: u32,
+        acknowledgment_number: u32,
+        flags: TCPFlags,
+        payload: Vec<u8>,
+        offset_secs: i64,
+    ) -> NetworkPacket {
+        let (source_ip, source_port) = source;
+        let (destination_ip, destination_port) = destination;
+        NetworkPacket {
+            timestamp: timestamp(offset_secs),
+            ethernet_layer: EthernetFrame {
+                source_mac: [0, 0, 0, 0, 0, 1],
+                destination_mac: [0, 0, 0, 0, 0, 2],
+                ethertype: 0x0800,
+                frame_check_sequence: 0,
+            },
+            body: PacketBody::Ip {
+                ip_layer: IpLayer::V4(IPv4Packet {
+                    version: 4,
+                    ihl: 5,
+                    dscp: 0,
+                    ecn: 0,
+                    total_length: 0,
+                    identification: 0,
+                    flags: IPv4Flags { reserved: false, dont_fragment: false, more_fragments: false },
+                    fragment_offset: 0,
+                    ttl: 64,
+                    protocol: 6,
+                    header_checksum: 0,
+                    source_ip,
+                    destination_ip,
+                    options: Vec::new(),
+                }),
+                transport_layer: TransportLayer::Tcp(TCPSegment {
+                    source_port,
+                    destination_port,
+                    sequence_number,
+                    acknowledgment_number,
+                    data_offset: 5,
+                    flags,
+                    window_size: 0,
+                    checksum: 0,
+                    urgent_pointer: 0,
+                    options: Vec::new(),
+                }),
+                application_layer: ApplicationData {
+                    protocol: ApplicationProtocol::Custom("unknown".to_string()),
+                    payload,
+                },
+            },
+        }
+    }
+
+    fn flags(syn: bool, ack: bool, fin: bool, rst: bool) -> TCPFlags {
+        TCPFlags { fin, syn, rst, psh: false, ack, urg: false, ece: false, cwr: false }
+    }
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 1];
+    const SERVER: [u8; 4] = [10, 0, 0, 2];
+
+    #[test]
+    fn reassembles_stream_even_when_no_syn_was_captured() {
+        // Capture starts mid-flow: no SYN ever observed for either direction.
+        let packets = vec![
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1000, 1, flags(false, true, false, false), b"GET ".to_vec(), 0),
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1100, 1, flags(false, true, false, false), b"/foo".to_vec(), 1),
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1300, 1, flags(false, true, false, false), b" bar".to_vec(), 2),
+        ];
+
+        let sessions = find_tcp_sessions(&packets);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].stream_a_to_b, b"GET /foo bar".to_vec());
+    }
+
+    #[test]
+    fn fin_with_ack_does_not_close_until_the_peer_actually_acks_it() {
+        let mut packets = vec![
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1000, 0, flags(true, false, false, false), Vec::new(), 0),
+            tcp_packet((SERVER, 80), (CLIENT, 4000), 2000, 1001, flags(true, true, false, false), Vec::new(), 1),
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1001, 2001, flags(false, true, false, false), Vec::new(), 2),
+            // Client FINs; every post-handshake segment carries ACK, so this is FIN+ACK
+            // but the server hasn't acknowledged this FIN yet.
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1001, 2001, flags(false, true, true, false), Vec::new(), 3),
+        ];
+
+        let sessions = find_tcp_sessions(&packets);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].state, FlowState::FinWait);
+        assert!(sessions[0].end_timestamp.is_none());
+
+        // Server now acks the client's FIN (seq 1001 + 1) while closing its own side.
+        packets.push(tcp_packet(
+            (SERVER, 80), (CLIENT, 4000), 2001, 1002, flags(false, true, true, false), Vec::new(), 4,
+        ));
+
+        let sessions = find_tcp_sessions(&packets);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].state, FlowState::Closed);
+        assert!(sessions[0].end_timestamp.is_some());
+    }
+
+    #[test]
+    fn stray_syn_after_handshake_does_not_regress_an_established_flow() {
+        let packets = vec![
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1000, 0, flags(true, false, false, false), Vec::new(), 0),
+            tcp_packet((SERVER, 80), (CLIENT, 4000), 2000, 1001, flags(true, true, false, false), Vec::new(), 1),
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1001, 2001, flags(false, true, false, false), Vec::new(), 2),
+            // A retransmitted/replayed SYN arrives after the handshake already completed.
+            tcp_packet((CLIENT, 4000), (SERVER, 80), 1000, 0, flags(true, false, false, false), Vec::new(), 3),
+        ];
+
+        let sessions = find_tcp_sessions(&packets);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].state, FlowState::Established);
+    }
+
+    fn udp_packet(
+        source: ([u8; 4], u16),
+        destination: ([u8; 4], u16),
+        payload: Vec<u8>,
+        offset_secs: i64,
+    ) -> NetworkPacket {
+        let (source_ip, source_port) = source;
+        let (destination_ip, destination_port) = destination;
+        NetworkPacket {
+            timestamp: timestamp(offset_secs),
+            ethernet_layer: EthernetFrame {
+                source_mac: [0, 0, 0, 0, 0, 1],
+                destination_mac: [0, 0, 0, 0, 0, 2],
+                ethertype: 0x0800,
+                frame_check_sequence: 0,
+            },
+            body: PacketBody::Ip {
+                ip_layer: IpLayer::V4(IPv4Packet {
+                    version: 4,
+                    ihl: 5,
+                    dscp: 0,
+                    ecn: 0,
+                    total_length: 0,
+                    identification: 0,
+                    flags: IPv4Flags { reserved: false, dont_fragment: false, more_fragments: false },
+                    fragment_offset: 0,
+                    ttl: 64,
+                    protocol: 17,
+                    header_checksum: 0,
+                    source_ip,
+                    destination_ip,
+                    options: Vec::new(),
+                }),
+                transport_layer: TransportLayer::Udp(UdpDatagram {
+                    source_port,
+                    destination_port,
+                    length: (8 + payload.len()) as u16,
+                    checksum: 0,
+                }),
+                application_layer: ApplicationData {
+                    protocol: ApplicationProtocol::DNS,
+                    payload,
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn find_udp_flows_groups_both_directions_into_one_flow() {
+        let packets = vec![
+            udp_packet((CLIENT, 5353), (SERVER, 53), b"query".to_vec(), 0),
+            udp_packet((SERVER, 53), (CLIENT, 5353), b"response".to_vec(), 1),
+            // A second, unrelated flow on a different port should stay separate.
+            udp_packet((CLIENT, 6000), (SERVER, 53), b"other".to_vec(), 2),
+        ];
+
+        let mut flows = find_udp_flows(&packets);
+        flows.sort_by_key(|flow| flow.packets.len());
+
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].packets.len(), 1);
+        assert_eq!(flows[1].packets.len(), 2);
+        assert_eq!(flows[1].start_timestamp, timestamp(0));
+        assert_eq!(flows[1].end_timestamp, timestamp(1));
+    }
+}